@@ -14,7 +14,11 @@
 
 use libc;
 use log::info;
+use std::fs;
+use std::net::IpAddr;
 use std::process::Command;
+use std::str::FromStr;
+use std::time::Duration;
 
 /// Checks if the current process is running with root privileges.
 ///
@@ -64,10 +68,18 @@ pub enum RouteType {
     Host,
 }
 
+/// The two covering routes used by split-default mode. Each is more specific than
+/// `0.0.0.0/0`, so together they win over the operator's real default without ever
+/// touching it.
+const SPLIT_ROUTE_LOWER_HALF: &str = "0.0.0.0/1";
+const SPLIT_ROUTE_UPPER_HALF: &str = "128.0.0.0/1";
+
 pub struct DefaultGateway {
-    origin: String,
-    remote: String,
-    default: bool,
+    // Dropping this runs the exact cleanup a SIGINT/SIGTERM/SIGHUP would have run, so a
+    // killed process tears down the same way a normal scope exit does. The routing state
+    // needed to do that (origin, remote, default, split, iface) lives in the closure's
+    // capture, not duplicated as fields here.
+    _teardown: teardown::TeardownToken,
 }
 
 impl DefaultGateway {
@@ -75,59 +87,100 @@ impl DefaultGateway {
     ///
     /// This function creates a new `DefaultGateway` instance, saves the original default gateway,
     /// adds a route to the remote host through the original default gateway, and optionally
-    /// replaces the default gateway with the provided `gateway`.
+    /// routes all traffic through the provided `gateway`.
+    ///
+    /// When `default` is set, `split` picks how that's done: `split = true` leaves the
+    /// system's real default route untouched and instead installs `0.0.0.0/1` and
+    /// `128.0.0.0/1` pointing at `gateway`, which are more specific than `0.0.0.0/0` and so
+    /// take priority while still leaving a working fallback route in place. `split = false`
+    /// keeps the old full-takeover behavior of deleting and replacing the default route.
     ///
     /// # Arguments
     ///
     /// * `gateway` - A string slice representing the new default gateway's IP address.
     /// * `remote` - A string slice representing the remote host's IP address.
-    /// * `default` - A boolean indicating whether to replace the current default gateway.
+    /// * `default` - A boolean indicating whether to route all traffic through `gateway`.
+    /// * `split` - A boolean indicating whether to use split-default routes instead of
+    ///   replacing the system's default route outright. Ignored when `default` is `false`.
     ///
     /// # Returns
     ///
     /// * `DefaultGateway` - A new instance of the `DefaultGateway` struct.
-    pub fn create(gateway: &str, remote: &str, default: bool) -> DefaultGateway {
+    pub fn create(gateway: &str, remote: &str, default: bool, split: bool) -> DefaultGateway {
         let origin = get_default_gateway().unwrap();
         info!("Original default gateway: {}.", origin);
-        add_route(RouteType::Host, remote, &origin).unwrap();
+        let iface = get_default_route_interface().ok();
+        add_route(RouteType::Host, remote, &origin, iface.as_deref()).unwrap();
         if default {
-            delete_default_gateway().unwrap();
-            set_default_gateway(gateway).unwrap();
+            if split {
+                info!("Installing split default routes via {}.", gateway);
+                add_route(RouteType::Net, SPLIT_ROUTE_LOWER_HALF, gateway, None).unwrap();
+                add_route(RouteType::Net, SPLIT_ROUTE_UPPER_HALF, gateway, None).unwrap();
+            } else {
+                delete_default_gateway().unwrap();
+                set_default_gateway(gateway).unwrap();
+            }
         }
-        DefaultGateway {
-            origin: origin,
-            remote: String::from(remote),
-            default: default,
+        let teardown_origin = origin.clone();
+        let teardown_remote = String::from(remote);
+        let teardown_iface = iface.clone();
+        let _teardown = teardown::register(move || {
+            DefaultGateway::teardown(
+                default,
+                split,
+                &teardown_origin,
+                &teardown_remote,
+                teardown_iface.as_deref(),
+            );
+        });
+        DefaultGateway { _teardown }
+    }
+
+    /// Restores the original routing table: the shared cleanup logic run either by `Drop`
+    /// (via `_teardown`) on a normal scope exit, or by the SIGINT/SIGTERM/SIGHUP handlers in
+    /// [`teardown`] when the process is killed instead.
+    ///
+    /// In split mode it just removes the two split routes, since the operator's default was
+    /// never touched; in full-takeover mode it restores the original default gateway. Either
+    /// way it removes the added route to the remote host, scoped to the same interface it was
+    /// pinned to, when one was found.
+    fn teardown(default: bool, split: bool, origin: &str, remote: &str, iface: Option<&str>) {
+        if default {
+            if split {
+                delete_route(RouteType::Net, SPLIT_ROUTE_LOWER_HALF, None).unwrap();
+                delete_route(RouteType::Net, SPLIT_ROUTE_UPPER_HALF, None).unwrap();
+            } else {
+                delete_default_gateway().unwrap();
+                set_default_gateway(origin).unwrap();
+            }
         }
+        delete_route(RouteType::Host, remote, iface).unwrap();
     }
 }
 
 impl Drop for DefaultGateway {
-    /// Restores the original default gateway and removes the added route when the `DefaultGateway`
-    /// instance is dropped.
+    /// Tears down the routes this guard installed.
     ///
-    /// This function is called automatically when the `DefaultGateway` instance goes out of scope.
-    /// It restores the original default gateway if it was replaced, and removes the added route
-    /// to the remote host.
+    /// The actual work happens when the `_teardown` field drops right after this method
+    /// returns, via the same closure the SIGINT/SIGTERM/SIGHUP handlers in [`teardown`] run
+    /// on a killed process, so both paths clean up identically and exactly once.
     fn drop(&mut self) {
-        if self.default {
-            delete_default_gateway().unwrap();
-            set_default_gateway(&self.origin).unwrap();
-        }
-        delete_route(RouteType::Host, &self.remote).unwrap();
+        info!("Cleaning up routes.");
     }
 }
 
 /// Deletes a route from the system routing table.
 ///
-/// This function deletes a route of the specified type from the system routing table using the
-/// `route` command. It is designed to work with Linux and macOS.
+/// On Linux this goes straight over an `AF_NETLINK`/`NETLINK_ROUTE` socket (see the
+/// [`netlink`] module); on macOS it still shells out to the `route` command.
 ///
 /// # Arguments
 ///
 /// * `route_type` - An enum value of `RouteType`, specifying whether the route is a network
 ///   or a host route.
 /// * `route` - A string slice representing the route's IP address or network.
+/// * `iface` - An optional interface name to scope the deletion to, as reported by
+///   [`get_default_route_interface`]. `None` matches on the route alone, as before.
 ///
 /// # Returns
 ///
@@ -137,42 +190,19 @@ impl Drop for DefaultGateway {
 /// # Panics
 ///
 /// This function will panic if the target OS is neither Linux nor macOS.
-pub fn delete_route(route_type: RouteType, route: &str) -> Result<(), String> {
-    let mode = match route_type {
-        RouteType::Net => "-net",
-        RouteType::Host => "-host",
-    };
-    info!("Deleting route: {} {}.", mode, route);
-    let status = if cfg!(target_os = "linux") {
-        Command::new("route")
-            .arg("-n")
-            .arg("del")
-            .arg(mode)
-            .arg(route)
-            .status()
-            .unwrap()
-    } else if cfg!(target_os = "macos") {
-        Command::new("route")
-            .arg("-n")
-            .arg("delete")
-            .arg(mode)
-            .arg(route)
-            .status()
-            .unwrap()
-    } else {
-        unimplemented!()
-    };
-    if status.success() {
-        Ok(())
-    } else {
-        Err(format!("route: {}", status))
-    }
+pub fn delete_route(route_type: RouteType, route: &str, iface: Option<&str>) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    return netlink::delete_route(route_type, route, iface);
+    #[cfg(target_os = "macos")]
+    return shell::delete_route(route_type, route, iface);
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    unimplemented!()
 }
 
 /// Adds a route to the system routing table.
 ///
-/// This function adds a route of the specified type to the system routing table using the
-/// `route` command. It is designed to work with Linux and macOS.
+/// On Linux this goes straight over an `AF_NETLINK`/`NETLINK_ROUTE` socket (see the
+/// [`netlink`] module); on macOS it still shells out to the `route` command.
 ///
 /// # Arguments
 ///
@@ -180,6 +210,10 @@ pub fn delete_route(route_type: RouteType, route: &str) -> Result<(), String> {
 ///   or a host route.
 /// * `route` - A string slice representing the route's IP address or network.
 /// * `gateway` - A string slice representing the gateway's IP address.
+/// * `iface` - An optional outgoing interface to bind the route to (`RTA_OIF` on Linux,
+///   `-ifp <iface>` on macOS), for multi-homed hosts where the gateway alone doesn't pin the
+///   route to the right link. `None` leaves route selection up to gateway reachability, as
+///   before.
 ///
 /// # Returns
 ///
@@ -189,42 +223,20 @@ pub fn delete_route(route_type: RouteType, route: &str) -> Result<(), String> {
 /// # Panics
 ///
 /// This function will panic if the target OS is neither Linux nor macOS.
-pub fn add_route(route_type: RouteType, route: &str, gateway: &str) -> Result<(), String> {
-    let mode = match route_type {
-        RouteType::Net => "-net",
-        RouteType::Host => "-host",
-    };
-    info!("Adding route: {} {} gateway {}.", mode, route, gateway);
-    let status = if cfg!(target_os = "linux") {
-        Command::new("route")
-            .arg("-n")
-            .arg("add")
-            .arg(mode)
-            .arg(route)
-            .arg("gw")
-            .arg(gateway)
-            .status()
-            .unwrap()
-    } else if cfg!(target_os = "macos") {
-        Command::new("route")
-            .arg("-n")
-            .arg("add")
-            .arg(mode)
-            .arg(route)
-            .arg(gateway)
-            .status()
-            .unwrap()
-    } else {
-        unimplemented!()
-    };
-    if status.success() {
-        Ok(())
-    } else {
-        Err(format!("route: {}", status))
-    }
+pub fn add_route(
+    route_type: RouteType,
+    route: &str,
+    gateway: &str,
+    iface: Option<&str>,
+) -> Result<(), String> {
+    #[cfg(target_os = "linux")]
+    return netlink::add_route(route_type, route, gateway, iface);
+    #[cfg(target_os = "macos")]
+    return shell::add_route(route_type, route, gateway, iface);
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    unimplemented!()
 }
 
-
 /// Sets the system's default gateway.
 ///
 /// # Arguments
@@ -236,7 +248,7 @@ pub fn add_route(route_type: RouteType, route: &str, gateway: &str) -> Result<()
 /// * `Result<(), String>` - Returns `Ok(())` if the default gateway is successfully set. If an error
 ///   occurs, returns `Err(String)` containing the error message.
 pub fn set_default_gateway(gateway: &str) -> Result<(), String> {
-    add_route(RouteType::Net, "default", gateway)
+    add_route(RouteType::Net, "default", gateway, None)
 }
 
 /// Deletes the system's default gateway.
@@ -246,12 +258,13 @@ pub fn set_default_gateway(gateway: &str) -> Result<(), String> {
 /// * `Result<(), String>` - Returns `Ok(())` if the default gateway is successfully deleted. If an error
 ///   occurs, returns `Err(String)` containing the error message.
 pub fn delete_default_gateway() -> Result<(), String> {
-    delete_route(RouteType::Net, "default")
+    delete_route(RouteType::Net, "default", None)
 }
 
 /// Retrieves the system's current default gateway.
 ///
-/// This function is designed to work with Linux and macOS.
+/// On Linux this dumps the routing table over netlink and returns the `RTA_GATEWAY` of the
+/// entry whose `rtm_dst_len == 0`; on macOS it still parses `route -n get default`.
 ///
 /// # Returns
 ///
@@ -262,44 +275,116 @@ pub fn delete_default_gateway() -> Result<(), String> {
 ///
 /// This function will panic if the target OS is neither Linux nor macOS.
 pub fn get_default_gateway() -> Result<String, String> {
-    let cmd = if cfg!(target_os = "linux") {
-        "ip -4 route list 0/0 | awk '{print $3}'"
-    } else if cfg!(target_os = "macos") {
-        "route -n get default | grep gateway | awk '{print $2}'"
-    } else {
-        unimplemented!()
-    };
-    let output = Command::new("bash").arg("-c").arg(cmd).output().unwrap();
-    if output.status.success() {
-        Ok(String::from_utf8(output.stdout)
-            .unwrap()
-            .trim_right()
-            .to_string())
-    } else {
-        Err(String::from_utf8(output.stderr).unwrap())
-    }
+    #[cfg(target_os = "linux")]
+    return netlink::get_default_gateway();
+    #[cfg(target_os = "macos")]
+    return shell::get_default_gateway();
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    unimplemented!()
 }
 
-/// Retrieves the public IP address of the system.
+/// Retrieves the interface associated with the system's default (`0.0.0.0/0`) route.
+///
+/// On Linux this reads `RTA_OIF` off the same netlink dump [`get_default_gateway`] uses; on
+/// macOS it parses `route -n get default`. On a multi-homed host this is what lets
+/// `DefaultGateway::create` pin the protective host route to `remote` onto the correct
+/// physical link, rather than relying on gateway reachability alone.
 ///
 /// # Returns
 ///
-/// * `Result<String, String>` - Returns `Ok(String)` containing the public IP address if successful.
-///   If an error occurs, returns `Err(String)` containing the error message.
-pub fn get_public_ip() -> Result<String, String> {
+/// * `Result<String, String>` - Returns `Ok(String)` containing the interface name if
+///   successful. If an error occurs, returns `Err(String)` containing the error message.
+///
+/// # Panics
+///
+/// This function will panic if the target OS is neither Linux nor macOS.
+pub fn get_default_route_interface() -> Result<String, String> {
+    #[cfg(target_os = "linux")]
+    return netlink::get_default_route_interface();
+    #[cfg(target_os = "macos")]
+    return shell::get_default_route_interface();
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    unimplemented!()
+}
+
+/// Public IP echo endpoints tried, in order, by [`get_public_ip`]. Each is expected to
+/// respond with nothing but the caller's IP address as plain text.
+const DEFAULT_PUBLIC_IP_ENDPOINTS: &[&str] = &[
+    "https://ipecho.net/plain",
+    "https://api.ipify.org",
+    "https://ifconfig.me/ip",
+    "https://icanhazip.com",
+];
+
+/// How long to wait on any single endpoint in [`DEFAULT_PUBLIC_IP_ENDPOINTS`] before moving
+/// on to the next one.
+const DEFAULT_PUBLIC_IP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Queries a single public-IP echo endpoint and validates that the response body parses as
+/// an `IpAddr`, so a captive portal or error page can't masquerade as a real answer.
+fn query_public_ip_endpoint(endpoint: &str, timeout: Duration) -> Result<IpAddr, String> {
+    // curl accepts fractional seconds for --max-time; truncating to whole seconds would turn
+    // any sub-second timeout into "0", which curl treats as "no limit" rather than "expire
+    // immediately".
     let output = Command::new("curl")
-        .arg("ipecho.net/plain")
+        .arg("-s")
+        .arg("--max-time")
+        .arg(timeout.as_secs_f64().to_string())
+        .arg(endpoint)
         .output()
-        .unwrap();
-    if output.status.success() {
-        Ok(String::from_utf8(output.stdout).unwrap())
-    } else {
-        Err(String::from_utf8(output.stderr).unwrap())
+        .map_err(|e| format!("curl: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("{}: curl exited with {}", endpoint, output.status));
+    }
+    let body = String::from_utf8(output.stdout).map_err(|e| format!("{}: {}", endpoint, e))?;
+    IpAddr::from_str(body.trim()).map_err(|e| format!("{}: invalid IP {:?}: {}", endpoint, body, e))
+}
+
+/// Retrieves the public IP address of the system.
+///
+/// Tries each of `endpoints`, in order, giving each up to `timeout` to respond, and moves on
+/// to the next one on a timeout, a request failure, or a response that doesn't parse as an
+/// `IpAddr`. This is what [`get_public_ip`] calls with [`DEFAULT_PUBLIC_IP_ENDPOINTS`] and
+/// [`DEFAULT_PUBLIC_IP_TIMEOUT`]; callers behind a restricted network can supply their own
+/// endpoint list and timeout instead.
+///
+/// # Returns
+///
+/// * `Result<IpAddr, String>` - Returns `Ok(IpAddr)` containing the public IP address if any
+///   endpoint succeeded. If every endpoint failed, returns `Err(String)` describing the last
+///   failure.
+pub fn discover_public_ip(endpoints: &[&str], timeout: Duration) -> Result<IpAddr, String> {
+    let mut last_err = "no public IP endpoints configured".to_string();
+    for endpoint in endpoints {
+        match query_public_ip_endpoint(endpoint, timeout) {
+            Ok(ip) => return Ok(ip),
+            Err(e) => {
+                info!("Public IP endpoint failed: {}", e);
+                last_err = e;
+            }
+        }
     }
+    Err(last_err)
+}
+
+/// Retrieves the public IP address of the system, trying
+/// [`DEFAULT_PUBLIC_IP_ENDPOINTS`] in order with a [`DEFAULT_PUBLIC_IP_TIMEOUT`] timeout
+/// each. See [`discover_public_ip`] to supply a custom endpoint list or timeout.
+///
+/// # Returns
+///
+/// * `Result<IpAddr, String>` - Returns `Ok(IpAddr)` containing the public IP address if
+///   successful. If an error occurs, returns `Err(String)` containing the error message.
+pub fn get_public_ip() -> Result<IpAddr, String> {
+    discover_public_ip(DEFAULT_PUBLIC_IP_ENDPOINTS, DEFAULT_PUBLIC_IP_TIMEOUT)
 }
 
 /// Retrieves the gateway for a specific route.
 ///
+/// Only used by `route_test`/`get_default_gateway_test` to check the routing table's actual
+/// state after `add_route`/`delete_route`, so it's `#[cfg(test)]` rather than a pub helper
+/// with no production caller.
+///
 /// # Arguments
 ///
 /// * `route` - A string slice representing the route's IP address or network.
@@ -308,17 +393,14 @@ pub fn get_public_ip() -> Result<String, String> {
 ///
 /// * `Result<String, String>` - Returns `Ok(String)` containing the gateway IP address if successful.
 ///   If an error occurs, returns `Err(String)` containing the error message.
+#[cfg(all(test, target_os = "linux"))]
 fn get_route_gateway(route: &str) -> Result<String, String> {
-    let cmd = format!("ip -4 route list {}", route);
-    let output = Command::new("bash").arg("-c").arg(cmd).output().unwrap();
-    if output.status.success() {
-        Ok(String::from_utf8(output.stdout)
-            .unwrap()
-            .trim_right()
-            .to_string())
-    } else {
-        Err(String::from_utf8(output.stderr).unwrap())
-    }
+    netlink::get_route_gateway(route)
+}
+
+#[cfg(all(test, target_os = "macos"))]
+fn get_route_gateway(route: &str) -> Result<String, String> {
+    shell::get_route_gateway(route)
 }
 
 /// Sets the system's DNS resolver.
@@ -359,11 +441,7 @@ pub fn set_dns(dns: &str) -> Result<String, String> {
 /// configured in the `/etc/sudoers` file.
 pub fn flush_dns() -> Result<String, String> {
     let cmd = "sudo systemd-resolve --flush-caches";
-    let output = Command::new("bash")
-        .arg("-c")
-        .arg(cmd)
-        .output()
-        .unwrap();
+    let output = Command::new("bash").arg("-c").arg(cmd).output().unwrap();
 
     if output.status.success() {
         Ok(String::from_utf8(output.stdout).unwrap())
@@ -372,6 +450,583 @@ pub fn flush_dns() -> Result<String, String> {
     }
 }
 
+const RESOLV_CONF_PATH: &str = "/etc/resolv.conf";
+
+/// RAII guard around the system's DNS configuration.
+///
+/// `set_dns` on its own has no way to give the user's original resolver back, so `create`
+/// snapshots the exact bytes of `/etc/resolv.conf` before overwriting it, and restores them
+/// on `Drop` (or on SIGINT/SIGTERM/SIGHUP, through the same [`teardown`] registry
+/// `DefaultGateway` uses). macOS additionally manages DNS through `scutil`/`networksetup`
+/// rather than a static `resolv.conf`; snapshotting that state is not implemented yet, so
+/// `DnsGuard` is gated to Linux rather than silently clobbering `/etc/resolv.conf` there
+/// without touching the resolver macOS actually consults.
+#[cfg(target_os = "linux")]
+pub struct DnsGuard {
+    // See `DefaultGateway`'s `_teardown` field: dropping this runs the restore exactly once,
+    // whether that happens via a normal scope exit or a killed process. The original bytes
+    // and pinned route live in the closure's capture, not duplicated as fields here.
+    _teardown: teardown::TeardownToken,
+}
+
+#[cfg(target_os = "linux")]
+impl DnsGuard {
+    /// Snapshots `/etc/resolv.conf`, points the resolver at `dns`, and returns a guard that
+    /// restores the original file on `Drop`.
+    ///
+    /// When `pin` is set, an explicit host route to `dns` via `gateway` is installed so DNS
+    /// queries are forced through the tunnel rather than leaking onto the physical interface
+    /// once the default route changes underneath them. That route is torn down alongside the
+    /// resolver on restore.
+    ///
+    /// # Arguments
+    ///
+    /// * `dns` - A string slice representing the IP address of the tunnel's DNS server.
+    /// * `gateway` - A string slice representing the tunnel gateway to pin the DNS route through.
+    /// * `pin` - A boolean indicating whether to add the host route that pins `dns` to `gateway`.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<DnsGuard, String>` - Returns `Ok(DnsGuard)` if the resolver was snapshotted
+    ///   and updated successfully. If an error occurs, returns `Err(String)` containing the
+    ///   error message.
+    pub fn create(dns: &str, gateway: &str, pin: bool) -> Result<DnsGuard, String> {
+        let original = fs::read(RESOLV_CONF_PATH).map_err(|e| format!("resolv.conf: {}", e))?;
+        set_dns(dns)?;
+        let pinned_route = if pin {
+            info!("Pinning DNS server {} through {}.", dns, gateway);
+            // `set_dns` above already overwrote resolv.conf, and no teardown has been
+            // registered yet to undo that — if the pin route fails to add, restore it
+            // ourselves instead of leaving the system pointed at the tunnel resolver with
+            // nothing left to fix it.
+            if let Err(e) = add_route(RouteType::Host, dns, gateway, None) {
+                let _ = fs::write(RESOLV_CONF_PATH, &original);
+                return Err(e);
+            }
+            Some(String::from(dns))
+        } else {
+            None
+        };
+
+        let teardown_original = original.clone();
+        let teardown_pinned_route = pinned_route.clone();
+        let _teardown = teardown::register(move || {
+            DnsGuard::teardown(&teardown_original, &teardown_pinned_route);
+        });
+
+        Ok(DnsGuard { _teardown })
+    }
+
+    /// Restores `/etc/resolv.conf` to its original bytes and removes the pinned DNS route,
+    /// if one was installed. Shared by `Drop` (via `_teardown`) and the SIGINT/SIGTERM/SIGHUP
+    /// handlers in [`teardown`].
+    fn teardown(original: &[u8], pinned_route: &Option<String>) {
+        if let Some(dns) = pinned_route {
+            delete_route(RouteType::Host, dns, None).unwrap();
+        }
+        fs::write(RESOLV_CONF_PATH, original).unwrap();
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for DnsGuard {
+    /// Restores the original DNS configuration.
+    ///
+    /// The actual work happens when `_teardown` drops right after this returns — see
+    /// `DefaultGateway::drop` for why that's where it lives.
+    fn drop(&mut self) {
+        info!("Restoring DNS configuration.");
+    }
+}
+
+/// Installs handlers for SIGINT, SIGTERM and SIGHUP that tear down every active
+/// `DefaultGateway` (and DNS guard) exactly once before the process exits, so a session
+/// killed uncleanly doesn't leave the machine with a hijacked default route, a dangling host
+/// route or a clobbered `/etc/resolv.conf`. Call this once at startup, before the tunnel
+/// takes over any routes.
+pub fn install_teardown_handlers() {
+    teardown::install_signal_handlers();
+}
+
+/// Native netlink routing backend for Linux.
+///
+/// Builds `RTM_NEWROUTE`/`RTM_DELROUTE`/`RTM_GETROUTE` messages and reads the reply
+/// attributes (`RTA_GATEWAY`, `RTA_DST`, `RTA_OIF`) directly over an
+/// `AF_NETLINK`/`NETLINK_ROUTE` socket, instead of forking `route`/`ip` and scraping
+/// stdout. This is what lets the process run under `CAP_NET_ADMIN` instead of requiring
+/// full root, since no shell or suid helper is ever spawned.
+#[cfg(target_os = "linux")]
+mod netlink {
+    use super::RouteType;
+    use log::info;
+    use netlink_packet_core::{
+        NetlinkMessage, NetlinkPayload, NLM_F_ACK, NLM_F_CREATE, NLM_F_DUMP, NLM_F_EXCL,
+        NLM_F_REQUEST,
+    };
+    use netlink_packet_route::route::{
+        RouteAddress, RouteAttribute, RouteMessage, RouteProtocol, RouteScope,
+        RouteType as NlRouteType,
+    };
+    use netlink_packet_route::{AddressFamily, RouteNetlinkMessage};
+    use netlink_sys::{protocols::NETLINK_ROUTE, Socket, SocketAddr};
+    use std::ffi::{CStr, CString};
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    fn iface_index(name: &str) -> Result<u32, String> {
+        let cname = CString::new(name).map_err(|e| format!("iface: {}", e))?;
+        let index = unsafe { libc::if_nametoindex(cname.as_ptr()) };
+        if index == 0 {
+            Err(format!("unknown interface: {}", name))
+        } else {
+            Ok(index)
+        }
+    }
+
+    fn iface_name(index: u32) -> Result<String, String> {
+        let mut buf = [0u8; libc::IF_NAMESIZE];
+        let ptr = unsafe { libc::if_indextoname(index, buf.as_mut_ptr() as *mut libc::c_char) };
+        if ptr.is_null() {
+            return Err(format!("unknown interface index: {}", index));
+        }
+        Ok(unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned())
+    }
+
+    fn open_socket() -> Result<Socket, String> {
+        let mut socket =
+            Socket::new(NETLINK_ROUTE).map_err(|e| format!("netlink socket: {}", e))?;
+        socket
+            .bind_auto()
+            .map_err(|e| format!("netlink bind: {}", e))?;
+        socket
+            .connect(&SocketAddr::new(0, 0))
+            .map_err(|e| format!("netlink connect: {}", e))?;
+        Ok(socket)
+    }
+
+    /// Sends `message` and collects the `NewRoute` replies.
+    ///
+    /// `dump` must be `true` for `NLM_F_DUMP` requests and `false` for everything else: a
+    /// dump reply is terminated by a trailing `Done`, but a plain (non-dump) `RTM_GETROUTE`
+    /// reply is just a single `NewRoute` with no `Done`/ACK following it, so waiting for one
+    /// there would block on `socket.recv()` forever. For `dump == false` we instead stop as
+    /// soon as that single route comes in.
+    fn request(
+        socket: &Socket,
+        mut message: NetlinkMessage<RouteNetlinkMessage>,
+        dump: bool,
+    ) -> Result<Vec<RouteMessage>, String> {
+        message.finalize();
+        let mut buf = vec![0u8; message.buffer_len()];
+        message.serialize(&mut buf);
+        socket
+            .send(&buf, 0)
+            .map_err(|e| format!("netlink send: {}", e))?;
+
+        let mut routes = Vec::new();
+        // `Vec<u8>::chunk_mut` (what `Socket::recv`'s `BufMut` bound reads from) only ever
+        // exposes spare capacity, so the buffer has to start out empty-with-capacity rather
+        // than pre-filled with zeros — a pre-filled `vec![0u8; 8192]` has no spare capacity
+        // at all and silently caps every read at the 64 bytes `BufMut` auto-reserves.
+        let mut rx: Vec<u8> = Vec::with_capacity(8192);
+        'recv: loop {
+            rx.clear();
+            socket
+                .recv(&mut rx, 0)
+                .map_err(|e| format!("netlink recv: {}", e))?;
+            let n = rx.len();
+            let mut offset = 0;
+            while offset < n {
+                let reply = NetlinkMessage::<RouteNetlinkMessage>::deserialize(&rx[offset..n])
+                    .map_err(|e| format!("netlink parse: {}", e))?;
+                let len = reply.header.length as usize;
+                match reply.payload {
+                    NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(route)) => {
+                        routes.push(route);
+                        if !dump {
+                            break 'recv;
+                        }
+                    }
+                    NetlinkPayload::Error(err) if err.code.is_some() => {
+                        return Err(format!("netlink error: {:?}", err));
+                    }
+                    NetlinkPayload::Done(_) | NetlinkPayload::Error(_) => break 'recv,
+                    _ => {}
+                }
+                offset += len;
+            }
+            if offset == 0 {
+                break;
+            }
+        }
+        Ok(routes)
+    }
+
+    /// Splits a route spec into its address and prefix length, accepting both a bare
+    /// `a.b.c.d` (host routes, or `RouteType::Net` routes that mean `/0`) and `a.b.c.d/n`
+    /// CIDR notation (e.g. the `0.0.0.0/1` / `128.0.0.0/1` split-default routes).
+    fn parse_route(route_type: &RouteType, route: &str) -> Result<(Option<Ipv4Addr>, u8), String> {
+        if route == "default" {
+            return Ok((None, 0));
+        }
+        if let Some((addr, len)) = route.split_once('/') {
+            let addr = Ipv4Addr::from_str(addr).map_err(|e| format!("route: {}", e))?;
+            let len = len.parse::<u8>().map_err(|e| format!("route: {}", e))?;
+            return Ok((Some(addr), len));
+        }
+        let addr = Ipv4Addr::from_str(route).map_err(|e| format!("route: {}", e))?;
+        let len = match route_type {
+            RouteType::Host => 32,
+            RouteType::Net => 0,
+        };
+        Ok((Some(addr), len))
+    }
+
+    /// Builds the `RTM_NEWROUTE`/`RTM_DELROUTE` payload shared by `add_route` and
+    /// `delete_route`: `rtm_dst_len = 32` with `RTA_DST` for a host route, `rtm_dst_len = 0`
+    /// for the default route, `RTA_GATEWAY` set when a gateway is supplied, `RTA_OIF` set
+    /// when an interface is supplied. `scope` is left up to the caller rather than guessed
+    /// here, since adds and deletes need different values (see `add_route`/`delete_route`).
+    fn route_message(
+        route_type: RouteType,
+        route: &str,
+        gateway: Option<Ipv4Addr>,
+        iface: Option<&str>,
+        scope: RouteScope,
+    ) -> Result<RouteMessage, String> {
+        let (dst, dst_len) = parse_route(&route_type, route)?;
+        let mut message = RouteMessage::default();
+        message.header.address_family = AddressFamily::Inet;
+        message.header.destination_prefix_length = dst_len;
+        message.header.table = libc::RT_TABLE_MAIN;
+        message.header.protocol = RouteProtocol::Boot;
+        message.header.kind = NlRouteType::Unicast;
+        message.header.scope = scope;
+        if let Some(dst) = dst {
+            message
+                .attributes
+                .push(RouteAttribute::Destination(RouteAddress::Inet(dst)));
+        }
+        if let Some(gw) = gateway {
+            message
+                .attributes
+                .push(RouteAttribute::Gateway(RouteAddress::Inet(gw)));
+        }
+        if let Some(iface) = iface {
+            message
+                .attributes
+                .push(RouteAttribute::Oif(iface_index(iface)?));
+        }
+        Ok(message)
+    }
+
+    pub fn add_route(
+        route_type: RouteType,
+        route: &str,
+        gateway: &str,
+        iface: Option<&str>,
+    ) -> Result<(), String> {
+        let gw = Ipv4Addr::from_str(gateway).map_err(|e| format!("gateway: {}", e))?;
+        info!("Adding route via netlink: {} gateway {}.", route, gateway);
+        let message = route_message(route_type, route, Some(gw), iface, RouteScope::Universe)?;
+        let mut nl_message = NetlinkMessage::from(RouteNetlinkMessage::NewRoute(message));
+        nl_message.header.flags = NLM_F_REQUEST | NLM_F_CREATE | NLM_F_EXCL | NLM_F_ACK;
+        request(&open_socket()?, nl_message, false)?;
+        Ok(())
+    }
+
+    pub fn delete_route(
+        route_type: RouteType,
+        route: &str,
+        iface: Option<&str>,
+    ) -> Result<(), String> {
+        info!("Deleting route via netlink: {}.", route);
+        // `rtm_scope` has to match what the route was stored with for `fib_table_delete` to
+        // find it, *unless* it's `RT_SCOPE_NOWHERE`, which the kernel special-cases to mean
+        // "match on destination/gateway/oif alone" — exactly what `ip route del` sends, and
+        // what we want here since every route this module adds carries a gateway and is
+        // therefore stored with `RouteScope::Universe`, not the `Link` scope a missing
+        // gateway would otherwise imply.
+        // Likewise `rtm_protocol`: the kernel only skips the protocol check when it's unset,
+        // and the operator's own default route is rarely stored as `RouteProtocol::Boot`.
+        let mut message = route_message(route_type, route, None, iface, RouteScope::NoWhere)?;
+        message.header.protocol = RouteProtocol::Unspec;
+        let mut nl_message = NetlinkMessage::from(RouteNetlinkMessage::DelRoute(message));
+        nl_message.header.flags = NLM_F_REQUEST | NLM_F_ACK;
+        request(&open_socket()?, nl_message, false)?;
+        Ok(())
+    }
+
+    /// Dumps the routing table and returns the `RTA_GATEWAY` of the entry whose
+    /// `rtm_dst_len == 0`.
+    pub fn get_default_gateway() -> Result<String, String> {
+        let mut message = RouteMessage::default();
+        message.header.address_family = AddressFamily::Inet;
+        let mut nl_message = NetlinkMessage::from(RouteNetlinkMessage::GetRoute(message));
+        nl_message.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+        let routes = request(&open_socket()?, nl_message, true)?;
+        for route in routes {
+            if route.header.destination_prefix_length != 0 {
+                continue;
+            }
+            for attr in &route.attributes {
+                if let RouteAttribute::Gateway(RouteAddress::Inet(gw)) = attr {
+                    return Ok(gw.to_string());
+                }
+            }
+        }
+        Err("no default route found".to_string())
+    }
+
+    /// Dumps the routing table and returns the interface name from the `RTA_OIF` of the
+    /// entry whose `rtm_dst_len == 0`.
+    pub fn get_default_route_interface() -> Result<String, String> {
+        let mut message = RouteMessage::default();
+        message.header.address_family = AddressFamily::Inet;
+        let mut nl_message = NetlinkMessage::from(RouteNetlinkMessage::GetRoute(message));
+        nl_message.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+        let routes = request(&open_socket()?, nl_message, true)?;
+        for route in routes {
+            if route.header.destination_prefix_length != 0 {
+                continue;
+            }
+            for attr in &route.attributes {
+                if let RouteAttribute::Oif(index) = attr {
+                    return iface_name(*index);
+                }
+            }
+        }
+        Err("no default route found".to_string())
+    }
+
+    /// Dumps the routing table and returns the `RTA_GATEWAY` of the entry whose destination
+    /// is exactly `route` (a `/32` host route, or the `rtm_dst_len == 0` entry for `"0/0"` or
+    /// `"default"`), like `ip -4 route list <route>`. Returns an empty string when no such
+    /// entry exists, so a deleted route reads as gone rather than as the default route a
+    /// lookup would fall back to.
+    #[cfg(test)]
+    pub fn get_route_gateway(route: &str) -> Result<String, String> {
+        let mut message = RouteMessage::default();
+        message.header.address_family = AddressFamily::Inet;
+        let mut wanted = (None, 0);
+        if route != "0/0" && route != "default" {
+            let dst = Ipv4Addr::from_str(route).map_err(|e| format!("route: {}", e))?;
+            wanted = (Some(dst), 32);
+        }
+        let mut nl_message = NetlinkMessage::from(RouteNetlinkMessage::GetRoute(message));
+        nl_message.header.flags = NLM_F_REQUEST | NLM_F_DUMP;
+        let routes = request(&open_socket()?, nl_message, true)?;
+        for route in routes {
+            let dst = route.attributes.iter().find_map(|attr| match attr {
+                RouteAttribute::Destination(RouteAddress::Inet(dst)) => Some(*dst),
+                _ => None,
+            });
+            if (dst, route.header.destination_prefix_length) != wanted {
+                continue;
+            }
+            for attr in &route.attributes {
+                if let RouteAttribute::Gateway(RouteAddress::Inet(gw)) = attr {
+                    return Ok(gw.to_string());
+                }
+            }
+        }
+        Ok(String::new())
+    }
+}
+
+/// `Command`-based routing backend, kept as the macOS implementation (there is no
+/// `PF_ROUTE`-over-a-plain-socket equivalent here yet) and as a documented fallback shape
+/// for any future non-Linux target.
+#[cfg(target_os = "macos")]
+mod shell {
+    use super::RouteType;
+    use log::info;
+    use std::process::Command;
+
+    pub fn delete_route(
+        route_type: RouteType,
+        route: &str,
+        iface: Option<&str>,
+    ) -> Result<(), String> {
+        let mode = match route_type {
+            RouteType::Net => "-net",
+            RouteType::Host => "-host",
+        };
+        info!("Deleting route: {} {}.", mode, route);
+        let mut command = Command::new("route");
+        command.arg("-n").arg("delete").arg(mode).arg(route);
+        if let Some(iface) = iface {
+            command.arg("-ifp").arg(iface);
+        }
+        let status = command.status().unwrap();
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("route: {}", status))
+        }
+    }
+
+    pub fn add_route(
+        route_type: RouteType,
+        route: &str,
+        gateway: &str,
+        iface: Option<&str>,
+    ) -> Result<(), String> {
+        let mode = match route_type {
+            RouteType::Net => "-net",
+            RouteType::Host => "-host",
+        };
+        info!("Adding route: {} {} gateway {}.", mode, route, gateway);
+        let mut command = Command::new("route");
+        command
+            .arg("-n")
+            .arg("add")
+            .arg(mode)
+            .arg(route)
+            .arg(gateway);
+        if let Some(iface) = iface {
+            command.arg("-ifp").arg(iface);
+        }
+        let status = command.status().unwrap();
+        if status.success() {
+            Ok(())
+        } else {
+            Err(format!("route: {}", status))
+        }
+    }
+
+    pub fn get_default_gateway() -> Result<String, String> {
+        let cmd = "route -n get default | grep gateway | awk '{print $2}'";
+        let output = Command::new("bash").arg("-c").arg(cmd).output().unwrap();
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout)
+                .unwrap()
+                .trim_right()
+                .to_string())
+        } else {
+            Err(String::from_utf8(output.stderr).unwrap())
+        }
+    }
+
+    pub fn get_default_route_interface() -> Result<String, String> {
+        let cmd = "route -n get default | grep interface | awk '{print $2}'";
+        let output = Command::new("bash").arg("-c").arg(cmd).output().unwrap();
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout)
+                .unwrap()
+                .trim_right()
+                .to_string())
+        } else {
+            Err(String::from_utf8(output.stderr).unwrap())
+        }
+    }
+
+    #[cfg(test)]
+    pub fn get_route_gateway(route: &str) -> Result<String, String> {
+        let cmd = format!(
+            "route -n get {}  | grep gateway | awk '{{print $2}}'",
+            route
+        );
+        let output = Command::new("bash").arg("-c").arg(cmd).output().unwrap();
+        if output.status.success() {
+            Ok(String::from_utf8(output.stdout)
+                .unwrap()
+                .trim_right()
+                .to_string())
+        } else {
+            Err(String::from_utf8(output.stderr).unwrap())
+        }
+    }
+}
+
+/// Idempotent, signal-safe teardown registry.
+///
+/// `DefaultGateway` (and `DnsGuard`) register a cleanup closure here when they take
+/// over the routing table or DNS config. [`install_signal_handlers`] makes SIGINT/SIGTERM/
+/// SIGHUP run every still-registered closure before the process exits, so a killed session
+/// doesn't leave the machine with a hijacked default route or a clobbered `resolv.conf`.
+/// Each closure runs at most once: whichever of the normal `Drop` path or the signal handler
+/// gets there first takes it, so the two can never double-delete the same route.
+mod teardown {
+    use std::sync::{Mutex, OnceLock};
+
+    type Cleanup = Box<dyn FnMut() + Send>;
+
+    static GUARDS: OnceLock<Mutex<Vec<Option<Cleanup>>>> = OnceLock::new();
+
+    fn guards() -> &'static Mutex<Vec<Option<Cleanup>>> {
+        GUARDS.get_or_init(|| Mutex::new(Vec::new()))
+    }
+
+    /// A handle to a cleanup closure registered with [`register`]. Dropping it runs the
+    /// closure, unless a signal handler installed by [`install_signal_handlers`] already ran
+    /// it first.
+    pub struct TeardownToken {
+        index: usize,
+    }
+
+    impl Drop for TeardownToken {
+        fn drop(&mut self) {
+            run(self.index);
+        }
+    }
+
+    /// Registers a cleanup closure and returns a token that runs it, at most once, when the
+    /// token is dropped.
+    pub fn register<F: FnMut() + Send + 'static>(cleanup: F) -> TeardownToken {
+        let mut guards = guards().lock().unwrap();
+        guards.push(Some(Box::new(cleanup)));
+        TeardownToken {
+            index: guards.len() - 1,
+        }
+    }
+
+    fn run(index: usize) {
+        let cleanup = guards()
+            .lock()
+            .unwrap()
+            .get_mut(index)
+            .and_then(Option::take);
+        if let Some(mut cleanup) = cleanup {
+            cleanup();
+        }
+    }
+
+    fn run_all() {
+        let len = guards().lock().unwrap().len();
+        for index in 0..len {
+            run(index);
+        }
+    }
+
+    /// Installs handlers for SIGINT, SIGTERM and SIGHUP that run every still-registered
+    /// cleanup closure before exiting. Call this once at startup, before taking over any
+    /// routes or DNS config.
+    ///
+    /// `run_all` locks a `Mutex`, allocates/deallocates, formats strings and does netlink/file
+    /// I/O, none of which is async-signal-safe, so it can't run inside the raw signal handler
+    /// itself (a signal landing on a thread already holding `GUARDS`'s lock, e.g. mid-
+    /// `register`, would deadlock the handler against itself). Instead `Signals::forever`
+    /// does the actual `sigaction`-level work and wakes a plain background thread through a
+    /// self-pipe, and `run_all` runs there, off the signal handler entirely.
+    pub fn install_signal_handlers() {
+        let mut signals = signal_hook::iterator::Signals::new([
+            signal_hook::consts::SIGINT,
+            signal_hook::consts::SIGTERM,
+            signal_hook::consts::SIGHUP,
+        ])
+        .unwrap();
+        std::thread::spawn(move || {
+            if signals.forever().next().is_some() {
+                run_all();
+                std::process::exit(1);
+            }
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::utils::*;
@@ -392,9 +1047,9 @@ mod tests {
     fn route_test() {
         assert!(is_root());
         let gw = get_default_gateway().unwrap();
-        add_route(RouteType::Host, "1.1.1.1", &gw).unwrap();
+        add_route(RouteType::Host, "1.1.1.1", &gw, None).unwrap();
         assert!(get_route_gateway("1.1.1.1").unwrap().contains(&*gw));
-        delete_route(RouteType::Host, "1.1.1.1").unwrap();
+        delete_route(RouteType::Host, "1.1.1.1", None).unwrap();
         assert!(!get_route_gateway("1.1.1.1").unwrap().contains(&*gw));
     }
     #[test]
@@ -402,4 +1057,23 @@ mod tests {
         assert!(is_root());
         set_dns("8.8.8.8").unwrap();
     }
+
+    #[test]
+    fn get_public_ip_test() {
+        get_public_ip().unwrap();
+    }
+
+    #[test]
+    fn discover_public_ip_falls_back_on_bad_endpoint() {
+        discover_public_ip(
+            &["https://127.0.0.1:1", "https://api.ipify.org"],
+            DEFAULT_PUBLIC_IP_TIMEOUT,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn discover_public_ip_fails_when_all_endpoints_fail() {
+        assert!(discover_public_ip(&["https://127.0.0.1:1"], DEFAULT_PUBLIC_IP_TIMEOUT).is_err());
+    }
 }